@@ -1,9 +1,35 @@
 mod math;
-use math::calculator;
+use math::calculator::{highlight_position, Calculator, PositionedError};
+use std::io::{self, Write};
 
+// Plain `stdin().read_line` rather than a `rustyline` editor: this tree has
+// no Cargo.toml to add the dependency to, so line editing/history is left
+// as a deliberate deviation from the request rather than a vendored stub.
 fn main() {
-    match calculator::calculate("2+3") {
-        Ok(result) => println!("2 + 3 = {}", result),
-        Err(e) => eprintln!("Error calculating expression: {}", e),
+    let mut calculator = Calculator::new();
+    let stdin = io::stdin();
+
+    loop {
+        print!("> ");
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+        if stdin.read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        match calculator.eval_line(line) {
+            Ok(result) => println!("{result}"),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                if let Some(pos) = e.downcast_ref::<PositionedError>().and_then(|pe| pe.pos) {
+                    eprintln!("{}", highlight_position(line, pos));
+                }
+            }
+        }
     }
 }
@@ -1,6 +1,7 @@
 use super::parser::Parser;
 use super::tokenizer::Tokenizer;
 use anyhow::{anyhow, Result};
+use std::collections::HashMap;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -11,29 +12,104 @@ pub enum CalcError {
     #[error("Division by zero in expression: {0}")]
     DivisionByZero(String),
 
-    #[error("Parser error: {0}")]
-    ParserError(&'static str),
+    #[error("Parser error: {0} at position {1}")]
+    ParserError(&'static str, usize),
 
     #[error("Evaluating error: {0}")]
     EvalError(String),
 }
 
+impl CalcError {
+    /// The source position to blame, if this variant carries one.
+    fn position(&self) -> Option<usize> {
+        match self {
+            CalcError::InvalidChar(_, pos) | CalcError::ParserError(_, pos) => Some(*pos),
+            CalcError::DivisionByZero(_) | CalcError::EvalError(_) => None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
-pub enum Token {
+pub enum TokenKind {
     Number(f64),
     Plus,
     Minus,
     Multiply,
     Divide,
+    Caret,
+    Percent,
+    DoubleSlash,
+    Eq,
+    NotEq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    BitAnd,
+    BitOr,
+    Shl,
+    Shr,
+    Xor,
+    Ident(String),
+    Assign,
+    Comma,
     LeftParen,
     RightParen,
 }
 
+/// A lexed token together with its 0-based position in the source string,
+/// so tokenize and parse errors can point back at the offending column.
+#[derive(Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub pos: usize,
+}
+
+impl Token {
+    pub fn new(kind: TokenKind, pos: usize) -> Self {
+        Token { kind, pos }
+    }
+}
+
+/// Renders a two-line, caret-underlined view of `input` pointing at `pos`,
+/// the way scripting-language runtimes surface a source position.
+pub fn highlight_position(input: &str, pos: usize) -> String {
+    format!("{}\n{}^", input, " ".repeat(pos))
+}
+
+/// A formatted tokenize/parse error that keeps the offending position
+/// around so callers (e.g. the REPL) can recover it with `downcast_ref` and
+/// render it with [`highlight_position`]; its `Display` text is the message.
+#[derive(Debug)]
+pub(crate) struct PositionedError {
+    message: String,
+    pub(crate) pos: Option<usize>,
+}
+
+impl std::fmt::Display for PositionedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for PositionedError {}
+
 #[derive(Debug)]
 pub enum Expr {
     Number(f64),
+    Ident(String),
+    Assign {
+        name: String,
+        value: Box<Expr>,
+    },
+    Call {
+        name: String,
+        args: Vec<Expr>,
+    },
     BinaryOp {
-        op: char,
+        op: &'static str,
         left: Box<Expr>,
         right: Box<Expr>,
     },
@@ -43,38 +119,215 @@ pub enum Expr {
     },
 }
 
+type BuiltinFn = Box<dyn Fn(&[f64]) -> f64>;
+
+fn builtin_functions() -> HashMap<String, (usize, BuiltinFn)> {
+    let mut functions: HashMap<String, (usize, BuiltinFn)> = HashMap::new();
+    functions.insert("sqrt".to_string(), (1, Box::new(|a| a[0].sqrt())));
+    functions.insert("abs".to_string(), (1, Box::new(|a| a[0].abs())));
+    functions.insert("floor".to_string(), (1, Box::new(|a| a[0].floor())));
+    functions.insert("ceil".to_string(), (1, Box::new(|a| a[0].ceil())));
+    functions.insert("sin".to_string(), (1, Box::new(|a| a[0].sin())));
+    functions.insert("cos".to_string(), (1, Box::new(|a| a[0].cos())));
+    functions.insert("ln".to_string(), (1, Box::new(|a| a[0].ln())));
+    functions.insert("log".to_string(), (1, Box::new(|a| a[0].log10())));
+    functions.insert("min".to_string(), (2, Box::new(|a| a[0].min(a[1]))));
+    functions.insert("max".to_string(), (2, Box::new(|a| a[0].max(a[1]))));
+    functions.insert("pow".to_string(), (2, Box::new(|a| a[0].powf(a[1]))));
+    functions
+}
+
+/// A stateful evaluator that persists variable bindings across calls to
+/// `eval_line`, as a REPL session would.
+pub struct Calculator {
+    env: HashMap<String, f64>,
+    functions: HashMap<String, (usize, BuiltinFn)>,
+}
+
+impl Calculator {
+    pub fn new() -> Self {
+        Calculator {
+            env: HashMap::new(),
+            functions: builtin_functions(),
+        }
+    }
+
+    /// Registers a user-defined function, overriding any built-in of the
+    /// same name.
+    pub fn register_function<F>(&mut self, name: &str, arity: usize, f: F)
+    where
+        F: Fn(&[f64]) -> f64 + 'static,
+    {
+        self.functions.insert(name.to_string(), (arity, Box::new(f)));
+    }
+
+    pub fn eval_line(&mut self, line: &str) -> Result<f64> {
+        let mut tokens = Tokenizer::tokenize(line).map_err(positioned_parse_error)?;
+        let ast = Parser::parse(&mut tokens, line.len()).map_err(positioned_parse_error)?;
+        eval(&ast, &mut self.env, &self.functions)
+            .map_err(|e| anyhow!("Failed to evaluate expression: {}", e))
+    }
+}
+
+impl Default for Calculator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub fn calculate(expr: &str) -> Result<f64> {
-    let mut tokens =
-        Tokenizer::tokenize(expr).map_err(|e| anyhow!("Failed to parse expression: {}", e))?;
-    let ast =
-        Parser::parse(&mut tokens).map_err(|e| anyhow!("Failed to parse expression: {}", e))?;
-    eval(&ast).map_err(|e| anyhow!("Failed to evaluate expression: {}", e))
+    let mut tokens = Tokenizer::tokenize(expr).map_err(positioned_parse_error)?;
+    let ast = Parser::parse(&mut tokens, expr.len()).map_err(positioned_parse_error)?;
+    let mut env = HashMap::new();
+    let functions = builtin_functions();
+    eval(&ast, &mut env, &functions).map_err(|e| anyhow!("Failed to evaluate expression: {}", e))
+}
+
+/// Wraps a tokenize/parse `CalcError` as a [`PositionedError`] so the
+/// position survives the conversion to `anyhow::Error`, while keeping the
+/// same "Failed to parse expression: ..." text callers already match on.
+fn positioned_parse_error(e: CalcError) -> anyhow::Error {
+    let pos = e.position();
+    anyhow::Error::new(PositionedError {
+        message: format!("Failed to parse expression: {e}"),
+        pos,
+    })
+}
+
+fn bool_to_f64(b: bool) -> f64 {
+    if b {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn as_i64(v: f64) -> Result<i64, CalcError> {
+    if v.fract() == 0.0 && v >= i64::MIN as f64 && v <= i64::MAX as f64 {
+        Ok(v as i64)
+    } else {
+        Err(CalcError::EvalError(
+            "bitwise operator requires integer operands".to_string(),
+        ))
+    }
 }
 
-fn eval(expr: &Expr) -> Result<f64, CalcError> {
+/// Shifts `lhs` by `rhs` bits (left if `shl`, right otherwise), rejecting a
+/// negative or overflowing shift amount instead of panicking like the plain
+/// `<<`/`>>` operators do on an out-of-range rhs.
+fn checked_shift(lhs: i64, rhs: i64, shl: bool) -> Result<i64, CalcError> {
+    u32::try_from(rhs)
+        .ok()
+        .filter(|&s| s < 64)
+        .and_then(|s| if shl { lhs.checked_shl(s) } else { lhs.checked_shr(s) })
+        .ok_or_else(|| {
+            CalcError::EvalError(format!("shift amount {rhs} out of range (must be 0..64)"))
+        })
+}
+
+fn eval(
+    expr: &Expr,
+    env: &mut HashMap<String, f64>,
+    functions: &HashMap<String, (usize, BuiltinFn)>,
+) -> Result<f64, CalcError> {
     match expr {
         Expr::Number(n) => Ok(*n),
+        Expr::Ident(name) => env
+            .get(name)
+            .copied()
+            .ok_or_else(|| CalcError::EvalError(format!("undefined variable: {name}"))),
+        Expr::Assign { name, value } => {
+            let val = eval(value, env, functions)?;
+            env.insert(name.clone(), val);
+            Ok(val)
+        }
+        Expr::Call { name, args } => {
+            let (arity, func) = functions
+                .get(name)
+                .ok_or_else(|| CalcError::EvalError(format!("unknown function: {name}")))?;
+            if args.len() != *arity {
+                return Err(CalcError::EvalError(format!(
+                    "{name} expects {arity} argument(s), got {}",
+                    args.len()
+                )));
+            }
+            let mut values = Vec::with_capacity(args.len());
+            for arg in args {
+                values.push(eval(arg, env, functions)?);
+            }
+            Ok(func(&values))
+        }
         Expr::UnaryOp { op, operand } => {
-            let val = eval(operand)?;
+            let val = eval(operand, env, functions)?;
             match op {
                 '+' => Ok(val),  //一元加法
                 '-' => Ok(-val), //一元减法
                 _ => Err(CalcError::EvalError(format!("Invalid operator: {}", op))),
             }
         }
-        Expr::BinaryOp { op, left, right } => {
-            let (left_val, right_val) = (eval(left)?, eval(right)?);
-            match op {
-                '+' => Ok(left_val + right_val),
-                '-' => Ok(left_val - right_val),
-                '*' => Ok(left_val * right_val),
-                '/' if right_val == 0.0 => {
-                    Err(CalcError::DivisionByZero("Division by zero".to_string()))
+        Expr::BinaryOp { op, left, right } => match *op {
+            "&&" => {
+                let left_val = eval(left, env, functions)?;
+                if left_val == 0.0 {
+                    Ok(0.0)
+                } else {
+                    Ok(bool_to_f64(eval(right, env, functions)? != 0.0))
                 }
-                '/' => Ok(left_val / right_val),
-                _ => Err(CalcError::EvalError(format!("Invalid operator: {}", op))),
             }
-        }
+            "||" => {
+                let left_val = eval(left, env, functions)?;
+                if left_val != 0.0 {
+                    Ok(1.0)
+                } else {
+                    Ok(bool_to_f64(eval(right, env, functions)? != 0.0))
+                }
+            }
+            _ => {
+                let (left_val, right_val) = (eval(left, env, functions)?, eval(right, env, functions)?);
+                match *op {
+                    "+" => Ok(left_val + right_val),
+                    "-" => Ok(left_val - right_val),
+                    "*" => Ok(left_val * right_val),
+                    "/" if right_val == 0.0 => {
+                        Err(CalcError::DivisionByZero("Division by zero".to_string()))
+                    }
+                    "/" => Ok(left_val / right_val),
+                    "%" if right_val == 0.0 => {
+                        Err(CalcError::DivisionByZero("Division by zero".to_string()))
+                    }
+                    "%" => Ok(left_val % right_val),
+                    "//" if right_val == 0.0 => {
+                        Err(CalcError::DivisionByZero("Division by zero".to_string()))
+                    }
+                    "//" => Ok((left_val / right_val).floor()),
+                    "^" => {
+                        let result = left_val.powf(right_val);
+                        if result.is_nan() || result.is_infinite() {
+                            Err(CalcError::EvalError(format!(
+                                "invalid result for {} ^ {}",
+                                left_val, right_val
+                            )))
+                        } else {
+                            Ok(result)
+                        }
+                    }
+                    "==" => Ok(bool_to_f64(left_val == right_val)),
+                    "!=" => Ok(bool_to_f64(left_val != right_val)),
+                    "<" => Ok(bool_to_f64(left_val < right_val)),
+                    "<=" => Ok(bool_to_f64(left_val <= right_val)),
+                    ">" => Ok(bool_to_f64(left_val > right_val)),
+                    ">=" => Ok(bool_to_f64(left_val >= right_val)),
+                    "&" => Ok((as_i64(left_val)? & as_i64(right_val)?) as f64),
+                    "|" => Ok((as_i64(left_val)? | as_i64(right_val)?) as f64),
+                    "^^" => Ok((as_i64(left_val)? ^ as_i64(right_val)?) as f64),
+                    "<<" => Ok(checked_shift(as_i64(left_val)?, as_i64(right_val)?, true)? as f64),
+                    ">>" => {
+                        Ok(checked_shift(as_i64(left_val)?, as_i64(right_val)?, false)? as f64)
+                    }
+                    _ => Err(CalcError::EvalError(format!("Invalid operator: {}", op))),
+                }
+            }
+        },
     }
 }
 
@@ -127,7 +380,7 @@ mod tests {
                 e.to_string(),
                 format!(
                     "Failed to parse expression: Invalid character: '{}' at position {}",
-                    '$', 2
+                    '$', 1
                 )
             );
         } else {
@@ -141,7 +394,7 @@ mod tests {
         if let Err(e) = result {
             assert_eq!(
                 e.to_string(),
-                "Failed to parse expression: Parser error: Expected closing parenthesis"
+                "Failed to parse expression: Parser error: Unexpected end of input at position 4"
             );
         } else {
             panic!("Expected an error, but got a result: {:?}", result);
@@ -173,4 +426,191 @@ mod tests {
         assert_eq!(calculate("--10").unwrap(), 10.0);
         assert_eq!(calculate("+-10").unwrap(), -10.0);
     }
+
+    #[test]
+    fn test_exponentiation() {
+        assert_eq!(calculate("2^3").unwrap(), 8.0);
+    }
+
+    #[test]
+    fn test_exponentiation_precedence_over_mul() {
+        assert_eq!(calculate("2*3^2").unwrap(), 18.0);
+    }
+
+    #[test]
+    fn test_exponentiation_right_associative() {
+        assert_eq!(calculate("2^3^2").unwrap(), 512.0);
+    }
+
+    #[test]
+    fn test_exponentiation_invalid_result() {
+        let result = calculate("(-1)^0.5");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_comparison_operators() {
+        assert_eq!(calculate("3 > 2").unwrap(), 1.0);
+        assert_eq!(calculate("3 < 2").unwrap(), 0.0);
+        assert_eq!(calculate("3 >= 3").unwrap(), 1.0);
+        assert_eq!(calculate("3 <= 2").unwrap(), 0.0);
+        assert_eq!(calculate("1 == 1").unwrap(), 1.0);
+        assert_eq!(calculate("1 != 1").unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        assert_eq!(calculate("(3 > 2) && (1 == 1)").unwrap(), 1.0);
+        assert_eq!(calculate("(3 > 2) && (1 == 2)").unwrap(), 0.0);
+        assert_eq!(calculate("(3 < 2) || (1 == 1)").unwrap(), 1.0);
+        assert_eq!(calculate("(3 < 2) || (1 == 2)").unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_logical_and_short_circuits() {
+        // `1/0` would error if evaluated, so a successful result here
+        // proves the right operand was skipped.
+        assert_eq!(calculate("0 && (1/0)").unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_hex_bin_octal_literals() {
+        assert_eq!(calculate("0xff").unwrap(), 255.0);
+        assert_eq!(calculate("0b101").unwrap(), 5.0);
+        assert_eq!(calculate("0o17").unwrap(), 15.0);
+    }
+
+    #[test]
+    fn test_bitwise_operators() {
+        assert_eq!(calculate("6 & 3").unwrap(), 2.0);
+        assert_eq!(calculate("6 | 1").unwrap(), 7.0);
+        assert_eq!(calculate("1 << 4").unwrap(), 16.0);
+        assert_eq!(calculate("16 >> 2").unwrap(), 4.0);
+    }
+
+    #[test]
+    fn test_bitwise_requires_integer_operands() {
+        let result = calculate("1.5 & 1");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_bitwise_xor() {
+        assert_eq!(calculate("6 ^^ 3").unwrap(), 5.0);
+    }
+
+    #[test]
+    fn test_shift_out_of_range_does_not_panic() {
+        assert!(calculate("1 << 64").is_err());
+        assert!(calculate("8 >> 70").is_err());
+        assert!(calculate("1 << -1").is_err());
+    }
+
+    #[test]
+    fn test_modulo() {
+        assert_eq!(calculate("17 % 5").unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_modulo_by_zero() {
+        assert!(calculate("17 % 0").is_err());
+    }
+
+    #[test]
+    fn test_floor_division() {
+        assert_eq!(calculate("17 // 5").unwrap(), 3.0);
+    }
+
+    #[test]
+    fn test_floor_division_by_zero() {
+        assert!(calculate("17 // 0").is_err());
+    }
+
+    #[test]
+    fn test_variables_persist_across_lines() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.eval_line("x = 5").unwrap(), 5.0);
+        assert_eq!(calc.eval_line("x * 2").unwrap(), 10.0);
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        let mut calc = Calculator::new();
+        let result = calc.eval_line("y + 1");
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Failed to evaluate expression: Evaluating error: undefined variable: y"
+        );
+    }
+
+    #[test]
+    fn test_reassignment() {
+        let mut calc = Calculator::new();
+        calc.eval_line("x = 1").unwrap();
+        calc.eval_line("x = x + 1").unwrap();
+        assert_eq!(calc.eval_line("x").unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_builtin_functions() {
+        assert_eq!(calculate("sqrt(16)").unwrap(), 4.0);
+        assert_eq!(calculate("min(3, 5)").unwrap(), 3.0);
+        assert_eq!(calculate("max(3, 5)").unwrap(), 5.0);
+        assert_eq!(calculate("pow(2, 10)").unwrap(), 1024.0);
+    }
+
+    #[test]
+    fn test_function_wrong_arity() {
+        let result = calculate("sqrt(1, 2)");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_unknown_function() {
+        let result = calculate("frobnicate(1)");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_register_user_function() {
+        let mut calc = Calculator::new();
+        calc.register_function("double", 1, |a| a[0] * 2.0);
+        assert_eq!(calc.eval_line("double(21)").unwrap(), 42.0);
+    }
+
+    #[test]
+    fn test_invalid_char_position_after_multi_digit_number() {
+        let result = calculate("123 + 45$6");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Failed to parse expression: Invalid character: '$' at position 8"
+        );
+    }
+
+    #[test]
+    fn test_unexpected_token_position() {
+        let result = calculate("(1 + 2 3)");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Failed to parse expression: Parser error: Expected closing parenthesis at position 7"
+        );
+    }
+
+    #[test]
+    fn test_highlight_position() {
+        assert_eq!(highlight_position("45$6", 2), "45$6\n  ^");
+    }
+
+    #[test]
+    fn test_eval_line_error_carries_position() {
+        let mut calc = Calculator::new();
+        let err = calc.eval_line("45$6").unwrap_err();
+        let pos = err
+            .downcast_ref::<PositionedError>()
+            .and_then(|e| e.pos)
+            .expect("tokenize error should carry a position");
+        assert_eq!(pos, 2);
+        assert_eq!(highlight_position("45$6", pos), "45$6\n  ^");
+    }
 }
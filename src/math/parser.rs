@@ -1,30 +1,134 @@
-use super::calculator::{CalcError, Expr, Token};
+use super::calculator::{CalcError, Expr, Token, TokenKind};
 use std::collections::VecDeque;
 
 pub struct Parser;
 
 impl Parser {
-    pub fn parse(tokens: &mut VecDeque<Token>) -> Result<Expr, CalcError> {
-        Self::parse_expr(tokens)
+    pub fn parse(tokens: &mut VecDeque<Token>, end_pos: usize) -> Result<Expr, CalcError> {
+        Self::parse_expr(tokens, end_pos)
     }
 
-    fn parse_expr(tokens: &mut VecDeque<Token>) -> Result<Expr, CalcError> {
-        let result = Self::parse_add_sub(tokens)?;
-        Ok(result)
+    /// Position to blame for the next token: its position if there is one,
+    /// otherwise (input has run out) the end of the source string.
+    fn pos(tokens: &VecDeque<Token>, end_pos: usize) -> usize {
+        tokens.front().map(|t| t.pos).unwrap_or(end_pos)
     }
 
-    fn parse_add_sub(tokens: &mut VecDeque<Token>) -> Result<Expr, CalcError> {
-        let mut left = Self::parse_mul_div(tokens)?;
+    fn parse_expr(tokens: &mut VecDeque<Token>, end_pos: usize) -> Result<Expr, CalcError> {
+        if let (
+            Some(Token {
+                kind: TokenKind::Ident(_),
+                ..
+            }),
+            Some(Token {
+                kind: TokenKind::Assign,
+                ..
+            }),
+        ) = (tokens.front(), tokens.get(1))
+        {
+            let name = match tokens.pop_front().unwrap().kind {
+                TokenKind::Ident(name) => name,
+                _ => unreachable!(),
+            };
+            tokens.pop_front(); // the '='
+            let value = Self::parse_expr(tokens, end_pos)?;
+            return Ok(Expr::Assign {
+                name,
+                value: Box::new(value),
+            });
+        }
+        Self::parse_or(tokens, end_pos)
+    }
+
+    fn parse_or(tokens: &mut VecDeque<Token>, end_pos: usize) -> Result<Expr, CalcError> {
+        let mut left = Self::parse_and(tokens, end_pos)?;
+
+        while matches!(tokens.front(), Some(Token { kind: TokenKind::Or, .. })) {
+            tokens.pop_front();
+            let right = Self::parse_and(tokens, end_pos)?;
+            left = Expr::BinaryOp {
+                op: "||",
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_and(tokens: &mut VecDeque<Token>, end_pos: usize) -> Result<Expr, CalcError> {
+        let mut left = Self::parse_comparison(tokens, end_pos)?;
+
+        while matches!(tokens.front(), Some(Token { kind: TokenKind::And, .. })) {
+            tokens.pop_front();
+            let right = Self::parse_comparison(tokens, end_pos)?;
+            left = Expr::BinaryOp {
+                op: "&&",
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_comparison(tokens: &mut VecDeque<Token>, end_pos: usize) -> Result<Expr, CalcError> {
+        let mut left = Self::parse_bitwise(tokens, end_pos)?;
+
+        while let Some(token) = tokens.front() {
+            let op = match token.kind {
+                TokenKind::Eq => "==",
+                TokenKind::NotEq => "!=",
+                TokenKind::Lt => "<",
+                TokenKind::Le => "<=",
+                TokenKind::Gt => ">",
+                TokenKind::Ge => ">=",
+                _ => break,
+            };
+            tokens.pop_front();
+            let right = Self::parse_bitwise(tokens, end_pos)?;
+            left = Expr::BinaryOp {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_bitwise(tokens: &mut VecDeque<Token>, end_pos: usize) -> Result<Expr, CalcError> {
+        let mut left = Self::parse_add_sub(tokens, end_pos)?;
 
         while let Some(token) = tokens.front() {
-            match token {
-                Token::Plus | Token::Minus => {
-                    let op = match tokens.pop_front().unwrap() {
-                        Token::Plus => '+',
-                        Token::Minus => '-',
+            let op = match token.kind {
+                TokenKind::BitAnd => "&",
+                TokenKind::BitOr => "|",
+                TokenKind::Xor => "^^",
+                TokenKind::Shl => "<<",
+                TokenKind::Shr => ">>",
+                _ => break,
+            };
+            tokens.pop_front();
+            let right = Self::parse_add_sub(tokens, end_pos)?;
+            left = Expr::BinaryOp {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_add_sub(tokens: &mut VecDeque<Token>, end_pos: usize) -> Result<Expr, CalcError> {
+        let mut left = Self::parse_mul_div(tokens, end_pos)?;
+
+        while let Some(token) = tokens.front() {
+            match token.kind {
+                TokenKind::Plus | TokenKind::Minus => {
+                    let op = match tokens.pop_front().unwrap().kind {
+                        TokenKind::Plus => "+",
+                        TokenKind::Minus => "-",
                         _ => unreachable!(),
                     };
-                    let right = Self::parse_mul_div(tokens)?;
+                    let right = Self::parse_mul_div(tokens, end_pos)?;
                     left = Expr::BinaryOp {
                         op,
                         left: Box::new(left),
@@ -37,18 +141,23 @@ impl Parser {
         Ok(left)
     }
 
-    fn parse_mul_div(tokens: &mut VecDeque<Token>) -> Result<Expr, CalcError> {
-        let mut left = Self::parse_primary(tokens)?;
+    fn parse_mul_div(tokens: &mut VecDeque<Token>, end_pos: usize) -> Result<Expr, CalcError> {
+        let mut left = Self::parse_pow(tokens, end_pos)?;
 
         while let Some(token) = tokens.front() {
-            match token {
-                Token::Multiply | Token::Divide => {
-                    let op = match tokens.pop_front().unwrap() {
-                        Token::Multiply => '*',
-                        Token::Divide => '/',
+            match token.kind {
+                TokenKind::Multiply
+                | TokenKind::Divide
+                | TokenKind::Percent
+                | TokenKind::DoubleSlash => {
+                    let op = match tokens.pop_front().unwrap().kind {
+                        TokenKind::Multiply => "*",
+                        TokenKind::Divide => "/",
+                        TokenKind::Percent => "%",
+                        TokenKind::DoubleSlash => "//",
                         _ => unreachable!(),
                     };
-                    let right = Self::parse_primary(tokens)?;
+                    let right = Self::parse_pow(tokens, end_pos)?;
                     left = Expr::BinaryOp {
                         op,
                         left: Box::new(left),
@@ -61,27 +170,91 @@ impl Parser {
         Ok(left)
     }
 
-    fn parse_primary(tokens: &mut VecDeque<Token>) -> Result<Expr, CalcError> {
-        match tokens.pop_front() {
-            Some(Token::Plus) => Ok(Expr::UnaryOp {
+    // Right-associative: `2^3^2` must parse as `2^(3^2)`, so the right
+    // operand recurses into parse_pow rather than looping.
+    fn parse_pow(tokens: &mut VecDeque<Token>, end_pos: usize) -> Result<Expr, CalcError> {
+        let base = Self::parse_primary(tokens, end_pos)?;
+
+        if matches!(tokens.front(), Some(Token { kind: TokenKind::Caret, .. })) {
+            tokens.pop_front();
+            let right = Self::parse_pow(tokens, end_pos)?;
+            Ok(Expr::BinaryOp {
+                op: "^",
+                left: Box::new(base),
+                right: Box::new(right),
+            })
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn parse_primary(tokens: &mut VecDeque<Token>, end_pos: usize) -> Result<Expr, CalcError> {
+        let blame = Self::pos(tokens, end_pos);
+        match tokens.pop_front().map(|t| t.kind) {
+            Some(TokenKind::Plus) => Ok(Expr::UnaryOp {
                 op: '+',
-                operand: Box::new(Self::parse_primary(tokens)?),
+                operand: Box::new(Self::parse_primary(tokens, end_pos)?),
             }),
-            Some(Token::Minus) => Ok(Expr::UnaryOp {
+            Some(TokenKind::Minus) => Ok(Expr::UnaryOp {
                 op: '-',
-                operand: Box::new(Self::parse_primary(tokens)?),
+                operand: Box::new(Self::parse_primary(tokens, end_pos)?),
             }),
-            Some(Token::Number(n)) => Ok(Expr::Number(n)),
-            Some(Token::LeftParen) => {
-                let expr = Self::parse_expr(tokens)?;
-                match tokens.pop_front() {
-                    Some(Token::RightParen) => Ok(expr),
-                    Some(_) => Err(CalcError::ParserError("Expected closing parenthesis")),
-                    None => Err(CalcError::ParserError("Unexpected end of input")),
+            Some(TokenKind::Number(n)) => Ok(Expr::Number(n)),
+            Some(TokenKind::Ident(name)) => {
+                if matches!(tokens.front(), Some(Token { kind: TokenKind::LeftParen, .. })) {
+                    tokens.pop_front();
+                    let args = Self::parse_call_args(tokens, end_pos)?;
+                    Ok(Expr::Call { name, args })
+                } else {
+                    Ok(Expr::Ident(name))
+                }
+            }
+            Some(TokenKind::LeftParen) => {
+                let expr = Self::parse_expr(tokens, end_pos)?;
+                let close_blame = Self::pos(tokens, end_pos);
+                match tokens.pop_front().map(|t| t.kind) {
+                    Some(TokenKind::RightParen) => Ok(expr),
+                    Some(_) => Err(CalcError::ParserError(
+                        "Expected closing parenthesis",
+                        close_blame,
+                    )),
+                    None => Err(CalcError::ParserError(
+                        "Unexpected end of input",
+                        close_blame,
+                    )),
+                }
+            }
+            Some(_) => Err(CalcError::ParserError(
+                "Expected number or parenthesis",
+                blame,
+            )),
+            None => Err(CalcError::ParserError("Unexpected end of input", blame)),
+        }
+    }
+
+    fn parse_call_args(tokens: &mut VecDeque<Token>, end_pos: usize) -> Result<Vec<Expr>, CalcError> {
+        let mut args = Vec::new();
+
+        if matches!(tokens.front(), Some(Token { kind: TokenKind::RightParen, .. })) {
+            tokens.pop_front();
+            return Ok(args);
+        }
+
+        loop {
+            args.push(Self::parse_or(tokens, end_pos)?);
+            let blame = Self::pos(tokens, end_pos);
+            match tokens.pop_front().map(|t| t.kind) {
+                Some(TokenKind::Comma) => continue,
+                Some(TokenKind::RightParen) => break,
+                Some(_) => {
+                    return Err(CalcError::ParserError(
+                        "Expected ',' or ')' in argument list",
+                        blame,
+                    ))
                 }
+                None => return Err(CalcError::ParserError("Unexpected end of input", blame)),
             }
-            Some(_) => Err(CalcError::ParserError("Expected number or parenthesis")),
-            None => Err(CalcError::ParserError("Unexpected end of input")),
         }
+        Ok(args)
     }
 }
@@ -1,18 +1,73 @@
-use super::calculator::{CalcError, Token};
+use super::calculator::{CalcError, Token, TokenKind};
 use std::collections::VecDeque;
+use std::iter::Peekable;
+use std::str::CharIndices;
 
 pub struct Tokenizer;
 
 impl Tokenizer {
     pub fn tokenize(expr: &str) -> Result<VecDeque<Token>, CalcError> {
-        let mut chars = expr.chars().peekable();
+        let mut chars = expr.char_indices().peekable();
         let mut tokens = VecDeque::new();
 
-        while let Some(&c) = chars.peek() {
+        while let Some(&(pos, c)) = chars.peek() {
             match c {
-                '0'..='9' | '.' => {
+                '0' => {
+                    chars.next();
+                    match chars.peek().map(|&(_, d)| d) {
+                        Some('x') | Some('X') => {
+                            chars.next();
+                            let kind = Self::read_radix_literal(&mut chars, 16, |d| {
+                                d.is_ascii_hexdigit()
+                            })?;
+                            tokens.push_back(Token::new(kind, pos));
+                        }
+                        Some('b') | Some('B') => {
+                            chars.next();
+                            let kind = Self::read_radix_literal(&mut chars, 2, |d| {
+                                d == '0' || d == '1'
+                            })?;
+                            tokens.push_back(Token::new(kind, pos));
+                        }
+                        Some('o') | Some('O') => {
+                            chars.next();
+                            let kind = Self::read_radix_literal(&mut chars, 8, |d| {
+                                ('0'..='7').contains(&d)
+                            })?;
+                            tokens.push_back(Token::new(kind, pos));
+                        }
+                        _ => {
+                            let mut num_str = String::from("0");
+                            while let Some(&(_, c)) = chars.peek() {
+                                if c.is_ascii_digit() || c == '.' {
+                                    num_str.push(c);
+                                    chars.next();
+                                } else {
+                                    break;
+                                }
+                            }
+                            let num = num_str
+                                .parse::<f64>()
+                                .map_err(|_| CalcError::ParserError("Failed to parse number", pos))?;
+                            tokens.push_back(Token::new(TokenKind::Number(num), pos));
+                        }
+                    }
+                }
+                c if c.is_alphabetic() || c == '_' => {
+                    let mut ident = String::new();
+                    while let Some(&(_, c)) = chars.peek() {
+                        if c.is_alphanumeric() || c == '_' {
+                            ident.push(c);
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    tokens.push_back(Token::new(TokenKind::Ident(ident), pos));
+                }
+                '1'..='9' | '.' => {
                     let mut num_str = String::new();
-                    while let Some(&c) = chars.peek() {
+                    while let Some(&(_, c)) = chars.peek() {
                         if c.is_ascii_digit() || c == '.' {
                             num_str.push(c);
                             chars.next();
@@ -22,40 +77,146 @@ impl Tokenizer {
                     }
                     let num = num_str
                         .parse::<f64>()
-                        .map_err(|_| CalcError::PaserError("Failed to parse number"))?;
-                    tokens.push_back(Token::Number(num));
+                        .map_err(|_| CalcError::ParserError("Failed to parse number", pos))?;
+                    tokens.push_back(Token::new(TokenKind::Number(num), pos));
                 }
                 '+' => {
-                    tokens.push_back(Token::Plus);
+                    tokens.push_back(Token::new(TokenKind::Plus, pos));
                     chars.next();
                 }
                 '-' => {
-                    tokens.push_back(Token::Minus);
+                    tokens.push_back(Token::new(TokenKind::Minus, pos));
                     chars.next();
                 }
                 '*' => {
-                    tokens.push_back(Token::Multiply);
+                    tokens.push_back(Token::new(TokenKind::Multiply, pos));
                     chars.next();
                 }
                 '/' => {
-                    tokens.push_back(Token::Divide);
+                    chars.next();
+                    if chars.peek().map(|&(_, d)| d) == Some('/') {
+                        chars.next();
+                        tokens.push_back(Token::new(TokenKind::DoubleSlash, pos));
+                    } else {
+                        tokens.push_back(Token::new(TokenKind::Divide, pos));
+                    }
+                }
+                '%' => {
+                    tokens.push_back(Token::new(TokenKind::Percent, pos));
+                    chars.next();
+                }
+                '^' => {
+                    chars.next();
+                    if chars.peek().map(|&(_, d)| d) == Some('^') {
+                        chars.next();
+                        tokens.push_back(Token::new(TokenKind::Xor, pos));
+                    } else {
+                        tokens.push_back(Token::new(TokenKind::Caret, pos));
+                    }
+                }
+                '=' => {
+                    chars.next();
+                    if chars.peek().map(|&(_, d)| d) == Some('=') {
+                        chars.next();
+                        tokens.push_back(Token::new(TokenKind::Eq, pos));
+                    } else {
+                        tokens.push_back(Token::new(TokenKind::Assign, pos));
+                    }
+                }
+                '!' => {
+                    chars.next();
+                    if chars.peek().map(|&(_, d)| d) == Some('=') {
+                        chars.next();
+                        tokens.push_back(Token::new(TokenKind::NotEq, pos));
+                    } else {
+                        return Err(CalcError::InvalidChar('!', pos));
+                    }
+                }
+                '<' => {
+                    chars.next();
+                    match chars.peek().map(|&(_, d)| d) {
+                        Some('=') => {
+                            chars.next();
+                            tokens.push_back(Token::new(TokenKind::Le, pos));
+                        }
+                        Some('<') => {
+                            chars.next();
+                            tokens.push_back(Token::new(TokenKind::Shl, pos));
+                        }
+                        _ => tokens.push_back(Token::new(TokenKind::Lt, pos)),
+                    }
+                }
+                '>' => {
+                    chars.next();
+                    match chars.peek().map(|&(_, d)| d) {
+                        Some('=') => {
+                            chars.next();
+                            tokens.push_back(Token::new(TokenKind::Ge, pos));
+                        }
+                        Some('>') => {
+                            chars.next();
+                            tokens.push_back(Token::new(TokenKind::Shr, pos));
+                        }
+                        _ => tokens.push_back(Token::new(TokenKind::Gt, pos)),
+                    }
+                }
+                '&' => {
+                    chars.next();
+                    if chars.peek().map(|&(_, d)| d) == Some('&') {
+                        chars.next();
+                        tokens.push_back(Token::new(TokenKind::And, pos));
+                    } else {
+                        tokens.push_back(Token::new(TokenKind::BitAnd, pos));
+                    }
+                }
+                '|' => {
+                    chars.next();
+                    if chars.peek().map(|&(_, d)| d) == Some('|') {
+                        chars.next();
+                        tokens.push_back(Token::new(TokenKind::Or, pos));
+                    } else {
+                        tokens.push_back(Token::new(TokenKind::BitOr, pos));
+                    }
+                }
+                ',' => {
+                    tokens.push_back(Token::new(TokenKind::Comma, pos));
                     chars.next();
                 }
                 '(' => {
-                    tokens.push_back(Token::LeftParen);
+                    tokens.push_back(Token::new(TokenKind::LeftParen, pos));
                     chars.next();
                 }
                 ')' => {
-                    tokens.push_back(Token::RightParen);
+                    tokens.push_back(Token::new(TokenKind::RightParen, pos));
                     chars.next();
                 }
                 ' ' => {
                     chars.next();
                 }
-                _ => return Err(CalcError::InvalidChar(c, chars.count())),
+                _ => return Err(CalcError::InvalidChar(c, pos)),
             }
         }
 
         Ok(tokens)
     }
+
+    fn read_radix_literal(
+        chars: &mut Peekable<CharIndices<'_>>,
+        radix: u32,
+        is_digit: fn(char) -> bool,
+    ) -> Result<TokenKind, CalcError> {
+        let start = chars.peek().map(|&(pos, _)| pos).unwrap_or(0);
+        let mut digits = String::new();
+        while let Some(&(_, d)) = chars.peek() {
+            if is_digit(d) {
+                digits.push(d);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        let n = i64::from_str_radix(&digits, radix)
+            .map_err(|_| CalcError::ParserError("Failed to parse integer literal", start))?;
+        Ok(TokenKind::Number(n as f64))
+    }
 }